@@ -0,0 +1,74 @@
+// Copyright 2024, Red Hat, Inc.
+// Author(s): Paolo Bonzini <pbonzini@redhat.com>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Error propagation for fallible QOM/qdev callbacks.
+//!
+//! QEMU surfaces failures to the user through an `Error **` out-parameter,
+//! filled in with `error_setg()` and forwarded to the caller with
+//! `error_propagate()`. [`Error`] gives Rust callbacks an ordinary value to
+//! build and return with [`Result`] instead of making them poke at that C API
+//! directly.
+
+use std::{
+    ffi::{CStr, CString},
+    fmt::Display,
+};
+
+use crate::bindings;
+
+/// An error to be propagated to C code through an `Error **` parameter.
+///
+/// Unlike [`bindings::Error`], which only ever appears behind a pointer
+/// handed out by `error_setg()`, this type can be built directly by Rust
+/// code; it only reaches into the C API when [`propagate()`](Error::propagate)
+/// is called.
+pub struct Error {
+    msg: CString,
+}
+
+impl Error {
+    /// Create an `Error` whose human-readable message is the `Display`
+    /// representation of `msg`.
+    pub fn new(msg: impl Display) -> Self {
+        // Error messages are not expected to contain embedded NULs; fall
+        // back to a fixed message instead of panicking if one sneaks in.
+        let msg = CString::new(msg.to_string())
+            .unwrap_or_else(|_| CString::new("(invalid error message)").unwrap());
+        Error { msg }
+    }
+
+    /// Store `self` into `*errp` via `error_setg()`.
+    ///
+    /// Does nothing if `errp` is NULL, mirroring `error_propagate()`.
+    ///
+    /// # Safety
+    ///
+    /// `errp` must be NULL or point to a valid `*mut bindings::Error`.
+    pub(crate) unsafe fn propagate(self, errp: *mut *mut bindings::Error) {
+        const FMT: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"%s\0") };
+
+        if errp.is_null() {
+            return;
+        }
+        unsafe {
+            bindings::error_setg(errp, FMT.as_ptr(), self.msg.as_ptr());
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Error::new(msg)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(msg: &str) -> Self {
+        Error::new(msg)
+    }
+}
+
+/// Convenient alias for the `Result` type returned by fallible QOM/qdev
+/// callbacks, such as [`DeviceImpl::REALIZE`](crate::qdev::DeviceImpl::REALIZE).
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,93 @@
+// Copyright 2024, Linaro Limited
+// Author(s): Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Bindings to create I2C slave devices and access I2C functionality from
+//! Rust.
+
+use std::{ffi::CStr, ptr::NonNull};
+
+pub use bindings::{I2CEvent, I2CSlave, I2CSlaveClass};
+
+use crate::{
+    bindings::{self, DeviceState},
+    prelude::*,
+    qom::{ClassInitImpl, ObjectImpl},
+};
+
+/// Trait providing the contents of [`I2CSlaveClass`].
+///
+/// This mirrors how [`DeviceImpl`](crate::qdev::DeviceImpl) provides the
+/// contents of `DeviceClass`: an I2C slave is a device, and these are the
+/// extra callbacks an `I2CSlave` subclass can hook into to take part in a
+/// transaction on the bus.
+pub trait I2CSlaveImpl: ObjectImpl {
+    /// If not `None`, called by the bus on every transition of the
+    /// transaction: when the master issues a START condition addressed to
+    /// this slave (`I2C_START_RECV`/`I2C_START_SEND`, distinguishing a
+    /// master read from a master write), and again when the transaction
+    /// ends (`I2C_FINISH`) or is NACKed (`I2C_NACK`).
+    const EVENT: Option<fn(&Self, I2CEvent)> = None;
+
+    /// Called by the bus when the master reads a byte from this slave.
+    fn recv(&self) -> u8;
+
+    /// Called by the bus when the master writes `data` to this slave.
+    /// Returning `Err` tells the bus to NACK the byte.
+    fn send(&self, data: u8) -> Result<(), ()>;
+}
+
+/// # Safety
+///
+/// We expect the FFI user of this function to pass a valid pointer that
+/// can be downcasted to type `T`. We also expect the device is
+/// readable/writeable from one thread at any time.
+unsafe extern "C" fn rust_i2c_event_fn<T: I2CSlaveImpl>(dev: *mut I2CSlave, event: I2CEvent) {
+    let state = NonNull::new(dev).unwrap().cast::<T>();
+    T::EVENT.unwrap()(unsafe { state.as_ref() }, event);
+}
+
+/// # Safety
+///
+/// We expect the FFI user of this function to pass a valid pointer that
+/// can be downcasted to type `T`. We also expect the device is
+/// readable/writeable from one thread at any time.
+unsafe extern "C" fn rust_i2c_recv_fn<T: I2CSlaveImpl>(dev: *mut I2CSlave) -> u8 {
+    let state = NonNull::new(dev).unwrap().cast::<T>();
+    T::recv(unsafe { state.as_ref() })
+}
+
+/// # Safety
+///
+/// We expect the FFI user of this function to pass a valid pointer that
+/// can be downcasted to type `T`. We also expect the device is
+/// readable/writeable from one thread at any time.
+unsafe extern "C" fn rust_i2c_send_fn<T: I2CSlaveImpl>(dev: *mut I2CSlave, data: u8) -> i32 {
+    let state = NonNull::new(dev).unwrap().cast::<T>();
+    match T::send(unsafe { state.as_ref() }, data) {
+        Ok(()) => 0,
+        Err(()) => -1,
+    }
+}
+
+impl<T> ClassInitImpl<I2CSlaveClass> for T
+where
+    T: ClassInitImpl<bindings::DeviceClass> + I2CSlaveImpl,
+{
+    fn class_init(ic: &mut I2CSlaveClass) {
+        if <T as I2CSlaveImpl>::EVENT.is_some() {
+            ic.event = Some(rust_i2c_event_fn::<T>);
+        }
+        ic.recv = Some(rust_i2c_recv_fn::<T>);
+        ic.send = Some(rust_i2c_send_fn::<T>);
+
+        <T as ClassInitImpl<bindings::DeviceClass>>::class_init(&mut ic.parent_class);
+    }
+}
+
+unsafe impl ObjectType for I2CSlave {
+    type Class = I2CSlaveClass;
+    const TYPE_NAME: &'static CStr =
+        unsafe { CStr::from_bytes_with_nul_unchecked(bindings::TYPE_I2C_SLAVE) };
+}
+qom_isa!(I2CSlave: DeviceState);
@@ -0,0 +1,10 @@
+// Copyright 2024, Linaro Limited
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Rust bindings and high-level wrappers for core QEMU APIs (QOM, qdev, and
+//! friends).
+
+pub mod error;
+pub mod i2c;
+pub mod qdev;
+pub mod regmap;
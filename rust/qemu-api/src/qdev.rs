@@ -5,18 +5,24 @@
 //! Bindings to create devices and access device functionality from Rust.
 
 use std::{
+    any::TypeId,
+    cell::RefCell,
+    collections::HashMap,
     ffi::{CStr, CString},
     os::raw::c_void,
     ptr::NonNull,
+    sync::{Mutex, OnceLock},
 };
 
 pub use bindings::{Clock, ClockEvent, DeviceClass, DeviceState, Property, ResetType};
 
 use crate::{
-    bindings::{self, Error, ResettableClass},
+    bindings::{self, ResettableClass},
     callbacks::FnCall,
     cell::bql_locked,
     chardev::Chardev,
+    error::{Error, Result},
+    irq::InterruptSource,
     prelude::*,
     qom::{ClassInitImpl, ObjectClass, ObjectImpl, Owned},
     vmstate::VMStateDescription,
@@ -87,12 +93,16 @@ unsafe extern "C" fn rust_resettable_exit_fn<T: ResettablePhasesImpl>(
 /// Trait providing the contents of [`DeviceClass`].
 pub trait DeviceImpl: ObjectImpl + ResettablePhasesImpl {
     /// _Realization_ is the second stage of device creation. It contains
-    /// all operations that depend on device properties and can fail (note:
-    /// this is not yet supported for Rust devices).
+    /// all operations that depend on device properties and can fail, such
+    /// as validating a combination of properties (e.g. a `Chardev` or
+    /// clock that conflicts with another device setting).
     ///
     /// If not `None`, the parent class's `realize` method is overridden
-    /// with the function pointed to by `REALIZE`.
-    const REALIZE: Option<fn(&Self)> = None;
+    /// with the function pointed to by `REALIZE`. Returning `Err` aborts
+    /// realization and reports the error to the caller through QEMU's
+    /// usual `Error **` mechanism, the same way a failing C `realize`
+    /// callback would.
+    const REALIZE: Option<fn(&Self) -> Result<()>> = None;
 
     /// An array providing the properties that the user can set on the
     /// device.  Not a `const` because referencing statics in constants
@@ -107,6 +117,53 @@ pub trait DeviceImpl: ObjectImpl + ResettablePhasesImpl {
     fn vmsd() -> Option<&'static VMStateDescription> {
         None
     }
+
+    /// Returns the [`FinalizeActions`] list embedded in this device's state
+    /// struct, if it has one. Override this, together with
+    /// [`HAS_FINALIZE_ACTIONS`](DeviceImpl::HAS_FINALIZE_ACTIONS), to opt
+    /// into [`DeviceMethods::add_finalize_action`]; the default of `None`
+    /// means the device has no devm-style cleanup actions to run.
+    fn finalize_actions(&self) -> Option<&FinalizeActions> {
+        None
+    }
+
+    /// Set to `true` by devices that override
+    /// [`finalize_actions`](DeviceImpl::finalize_actions) to opt into
+    /// [`DeviceMethods::add_finalize_action`]. Gates whether `class_init`
+    /// installs the Rust `instance_finalize` hook at all, so devices that
+    /// never register any action do not have their QOM finalizer
+    /// overridden.
+    const HAS_FINALIZE_ACTIONS: bool = false;
+}
+
+/// A LIFO list of cleanup closures registered through
+/// [`DeviceMethods::add_finalize_action`], modeled on the Linux kernel's
+/// `devm_add_action()`.
+///
+/// Device state structs that want to use `add_finalize_action` embed one
+/// of these fields and expose it by overriding
+/// [`DeviceImpl::finalize_actions`].
+#[derive(Default)]
+pub struct FinalizeActions(RefCell<Vec<Box<dyn FnOnce()>>>);
+
+impl FinalizeActions {
+    fn push(&self, action: Box<dyn FnOnce()>) {
+        assert!(bql_locked());
+        self.0.borrow_mut().push(action);
+    }
+
+    /// Run every registered action in reverse (LIFO) registration order,
+    /// consuming them.
+    ///
+    /// This is called from the device's QOM `instance_finalize` hook,
+    /// which runs whether or not the device ever finished realizing; it
+    /// is not meant to be invoked directly by device code.
+    fn run_all(&self) {
+        assert!(bql_locked());
+        for action in self.0.borrow_mut().drain(..).rev() {
+            action();
+        }
+    }
 }
 
 /// # Safety
@@ -116,9 +173,60 @@ pub trait DeviceImpl: ObjectImpl + ResettablePhasesImpl {
 /// We expect the FFI user of this function to pass a valid pointer that
 /// can be downcasted to type `T`. We also expect the device is
 /// readable/writeable from one thread at any time.
-unsafe extern "C" fn rust_realize_fn<T: DeviceImpl>(dev: *mut DeviceState, _errp: *mut *mut Error) {
+unsafe extern "C" fn rust_realize_fn<T: DeviceImpl>(
+    dev: *mut DeviceState,
+    errp: *mut *mut bindings::Error,
+) {
     let state = NonNull::new(dev).unwrap().cast::<T>();
-    T::REALIZE.unwrap()(unsafe { state.as_ref() });
+    if let Err(err) = T::REALIZE.unwrap()(unsafe { state.as_ref() }) {
+        // SAFETY: the caller guarantees that errp is NULL or a valid
+        // pointer to a `*mut bindings::Error`.
+        unsafe {
+            err.propagate(errp);
+        }
+    }
+}
+
+type InstanceFinalizeFn = unsafe extern "C" fn(*mut Object);
+
+/// Per-`T` storage for whatever `instance_finalize` the parent
+/// `ObjectClass::class_init()` had already installed (e.g. the standard
+/// finalizer that drops embedded Rust fields) before
+/// [`rust_device_finalize_fn`] replaced it. A `static` defined inside a
+/// generic function is shared by every instantiation of `T`, so the cells
+/// are kept in a map keyed by `TypeId`; this gives every device type that
+/// opts into `HAS_FINALIZE_ACTIONS` its own cell.
+fn parent_instance_finalize<T: 'static>() -> &'static OnceLock<Option<InstanceFinalizeFn>> {
+    type FinalizeCell = &'static OnceLock<Option<InstanceFinalizeFn>>;
+    static CELLS: OnceLock<Mutex<HashMap<TypeId, FinalizeCell>>> = OnceLock::new();
+
+    let mut cells = CELLS.get_or_init(Default::default).lock().unwrap();
+    let cell = *cells
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| Box::leak(Box::new(OnceLock::new())));
+    cell
+}
+
+/// # Safety
+///
+/// This function is only called through the QOM machinery and
+/// used by the `ClassInitImpl<DeviceClass>` trait. It is installed as the
+/// object's `instance_finalize`, which QOM calls unconditionally when the
+/// object is destroyed, regardless of whether `REALIZE` ever ran or
+/// succeeded.
+/// We expect the FFI user of this function to pass a valid pointer that
+/// can be downcasted to type `T`. We also expect the device is
+/// readable/writeable from one thread at any time.
+unsafe extern "C" fn rust_device_finalize_fn<T: DeviceImpl>(obj: *mut Object) {
+    let state = NonNull::new(obj).unwrap().cast::<T>();
+    if let Some(actions) = unsafe { state.as_ref() }.finalize_actions() {
+        actions.run_all();
+    }
+    // Chain to whatever instance_finalize the parent class installed
+    // (e.g. to drop embedded Rust fields), instead of discarding it.
+    if let Some(parent_finalize) = parent_instance_finalize::<T>().get().copied().flatten() {
+        unsafe { parent_finalize(obj) };
+    }
 }
 
 unsafe impl InterfaceType for ResettableClass {
@@ -163,6 +271,16 @@ where
 
         ResettableClass::interface_init::<T, DeviceState>(dc);
         <T as ClassInitImpl<ObjectClass>>::class_init(&mut dc.parent_class);
+
+        // Installed last, after the parent ObjectClass::class_init() call
+        // above, so there is something to capture: only devices that
+        // actually use add_finalize_action() pay for an instance_finalize
+        // override, and rust_device_finalize_fn chains to whatever
+        // finalizer the parent class installed instead of discarding it.
+        if <T as DeviceImpl>::HAS_FINALIZE_ACTIONS {
+            parent_instance_finalize::<T>().get_or_init(|| dc.parent_class.instance_finalize);
+            dc.parent_class.instance_finalize = Some(rust_device_finalize_fn::<T>);
+        }
     }
 }
 
@@ -311,6 +429,71 @@ where
         }
     }
 
+    /// Create `n` unnamed input GPIO lines, invoking `cb` with `self`, the
+    /// line number and the new level every time one of them changes.
+    #[inline]
+    fn init_gpio_in<F: for<'a> FnCall<(&'a Self::Target, u32, i32)>>(&self, n: u32, _cb: &F) {
+        fn do_init_gpio_in(
+            dev: *mut DeviceState,
+            cb: Option<unsafe extern "C" fn(*mut c_void, i32, i32)>,
+            n: u32,
+        ) {
+            assert!(bql_locked());
+
+            // SAFETY: the opaque pointer passed to qdev_init_gpio_in() is
+            // `dev` itself, which rust_gpio_in_cb casts back to `T` on
+            // every call; the lines are owned by `dev` and torn down by
+            // qdev_finalize_gpio_list() before the device is finalized.
+            unsafe {
+                bindings::qdev_init_gpio_in(dev, cb, n as i32);
+            }
+        }
+
+        let cb: Option<unsafe extern "C" fn(*mut c_void, i32, i32)> = if F::is_some() {
+            unsafe extern "C" fn rust_gpio_in_cb<T, F: for<'a> FnCall<(&'a T, u32, i32)>>(
+                opaque: *mut c_void,
+                line: i32,
+                level: i32,
+            ) {
+                // SAFETY: the opaque is "this", which is indeed a pointer to T
+                F::call((unsafe { &*(opaque.cast::<T>()) }, line as u32, level))
+            }
+            Some(rust_gpio_in_cb::<Self::Target, F>)
+        } else {
+            None
+        };
+
+        do_init_gpio_in(self.as_mut_ptr(), cb, n)
+    }
+
+    /// Create `n` named output GPIO lines, returning the
+    /// [`InterruptSource`] objects used to raise or lower them.
+    #[inline]
+    fn init_gpio_out(&self, name: &str, n: u32) -> Owned<[InterruptSource]> {
+        assert!(bql_locked());
+        let cstr = CString::new(name).unwrap();
+        let mut pins: Vec<*mut bindings::IRQState> = vec![std::ptr::null_mut(); n as usize];
+
+        // SAFETY: `pins` has room for the `n` qemu_irq that
+        // qdev_init_gpio_out_named() fills in; the lines are added as
+        // children of `self` and stay alive until the device is
+        // finalized.
+        unsafe {
+            bindings::qdev_init_gpio_out_named(
+                self.as_mut_ptr(),
+                pins.as_mut_ptr(),
+                cstr.as_ptr(),
+                n as i32,
+            );
+        }
+
+        let sources: Box<[InterruptSource]> = pins
+            .into_iter()
+            .map(|irq| unsafe { InterruptSource::from(&*irq) })
+            .collect();
+        Owned::from(sources)
+    }
+
     fn prop_set_chr(&self, propname: &str, chr: &Owned<Chardev>) {
         assert!(bql_locked());
         let c_propname = CString::new(propname).unwrap();
@@ -318,6 +501,29 @@ where
             bindings::qdev_prop_set_chr(self.as_mut_ptr(), c_propname.as_ptr(), chr.as_mut_ptr());
         }
     }
+
+    /// Register `action` to run when the device is finalized, modeled on
+    /// the Linux kernel's `devm_add_action()`. Actions run in LIFO order,
+    /// giving Rust device authors a safe, RAII-adjacent way to release
+    /// host resources (file descriptors, timers, backend connections)
+    /// acquired during `REALIZE`, without hand-writing an `ObjectImpl`
+    /// finalizer and tracking by hand what needs tearing down.
+    ///
+    /// Requires `Self::Target` to override [`DeviceImpl::finalize_actions`]
+    /// with a reference to a [`FinalizeActions`] field embedded in its
+    /// state struct, and to set
+    /// [`HAS_FINALIZE_ACTIONS`](DeviceImpl::HAS_FINALIZE_ACTIONS) to `true`;
+    /// without the latter the actions are registered but never run.
+    fn add_finalize_action<F: FnOnce() + 'static>(&self, action: F)
+    where
+        Self::Target: DeviceImpl,
+    {
+        assert!(bql_locked());
+        let actions = self.finalize_actions().expect(
+            "add_finalize_action() requires DeviceImpl::finalize_actions() to be overridden",
+        );
+        actions.push(Box::new(action));
+    }
 }
 
 impl<R: ObjectDeref> DeviceMethods for R where R::Target: IsA<DeviceState> {}
@@ -0,0 +1,289 @@
+// Copyright 2024, Linaro Limited
+// Author(s): Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! A declarative register map, inspired by the Linux kernel's `regmap`
+//! abstraction.
+//!
+//! Instead of hand-writing a `match` over byte offsets in an MMIO
+//! read/write handler, a device describes its register file as a static
+//! [`RegisterDesc`] table wrapped in a [`RegisterMap`], and lets
+//! [`RegisterMap::dispatch_read`]/[`RegisterMap::dispatch_write`] do the
+//! decoding, width/alignment checking, and access-flag enforcement.
+//! Registers that are pure storage (no side effect on access) need no
+//! callback at all; their value simply lives in the map's
+//! [`RegisterCache`].
+
+use std::cell::Cell;
+
+/// Access permissions for a [`RegisterDesc`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Read-only; writes are silently dropped.
+    RO,
+    /// Write-only; reads return `0`.
+    WO,
+    /// Readable and writable.
+    RW,
+    /// Write-1-to-clear: bits written as `1` are cleared in the stored
+    /// value, and writes to a `0` bit are ignored. Readable like `RW`.
+    W1C,
+}
+
+/// Describes a single register in a [`RegisterMap`].
+///
+/// `read` and `write`, when present, let the device react to an access
+/// instead of (or in addition to) the map's own cached storage:
+///
+/// * `read(dev, cached_value) -> value_to_return`
+/// * `write(dev, cached_value, incoming_data)`
+///
+/// Registers that leave both as `None` are pure storage: their value
+/// lives entirely in the [`RegisterMap`]'s backing [`RegisterCache`].
+pub struct RegisterDesc<T> {
+    /// Byte offset of the register within the map.
+    pub offset: u64,
+    /// Width of the register in bytes (1, 2, 4 or 8).
+    pub width: u8,
+    /// Access permissions enforced by `dispatch_read`/`dispatch_write`.
+    pub access: Access,
+    /// Value the register reads back as before it is ever written.
+    pub reset: u64,
+    /// Optional read side effect; see the struct documentation.
+    pub read: Option<fn(&T, u64) -> u64>,
+    /// Optional write side effect; see the struct documentation.
+    pub write: Option<fn(&T, u64, u64)>,
+}
+
+/// Per-instance backing storage for the registers of a [`RegisterMap`]
+/// that have no explicit `read`/`write` callback.
+///
+/// Built once (typically during device instance init) with
+/// [`RegisterMap::new_cache`], and threaded through every
+/// `dispatch_read`/`dispatch_write` call afterwards.
+pub struct RegisterCache(Box<[Cell<u64>]>);
+
+/// A static table of [`RegisterDesc`] entries describing a device's MMIO
+/// register file, together with the dispatch logic to decode an access
+/// against it.
+pub struct RegisterMap<T> {
+    registers: &'static [RegisterDesc<T>],
+}
+
+impl<T> RegisterMap<T> {
+    /// Wrap a static, offset-sorted register table.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, panics if `registers` is not sorted by `offset`
+    /// or if any two registers overlap.
+    pub const fn new(registers: &'static [RegisterDesc<T>]) -> Self {
+        debug_assert_no_overlap(registers);
+        RegisterMap { registers }
+    }
+
+    /// Build the per-instance cache, seeded with each register's reset
+    /// value.
+    pub fn new_cache(&self) -> RegisterCache {
+        RegisterCache(
+            self.registers
+                .iter()
+                .map(|r| Cell::new(r.reset))
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+        )
+    }
+
+    /// Find the index of the register covering `offset`, if any.
+    fn find(&self, offset: u64) -> Option<usize> {
+        self.registers
+            .binary_search_by(|r| {
+                if offset < r.offset {
+                    std::cmp::Ordering::Greater
+                } else if offset >= r.offset + r.width as u64 {
+                    std::cmp::Ordering::Less
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .ok()
+    }
+
+    /// Decode an MMIO read of `size` bytes at `offset`.
+    ///
+    /// Accesses narrower than the containing register are masked and
+    /// shifted out of its full value, rather than being treated as a
+    /// distinct register. Reads that hit no register, that spill past the
+    /// end of the register they start in, or that target a `WO` register,
+    /// return `0`.
+    pub fn dispatch_read(&self, dev: &T, cache: &RegisterCache, offset: u64, size: u32) -> u64 {
+        let Some(idx) = self.find(offset) else {
+            return 0;
+        };
+        let reg = &self.registers[idx];
+        if offset + size as u64 > reg.offset + reg.width as u64 {
+            return 0;
+        }
+        if reg.access == Access::WO {
+            return 0;
+        }
+
+        let cached = cache.0[idx].get();
+        let value = match reg.read {
+            Some(read) => read(dev, cached),
+            None => cached,
+        };
+
+        let shift = (offset - reg.offset) * 8;
+        let mask = width_mask(size);
+        (value >> shift) & mask
+    }
+
+    /// Decode an MMIO write of `size` bytes at `offset` with value `val`.
+    ///
+    /// Accesses narrower than the containing register are merged against
+    /// the register's current cached value rather than clobbering the
+    /// untouched bytes. Writes that hit no register, that spill past the
+    /// end of the register they start in, or that target an `RO` register,
+    /// are silently dropped, matching how real register files ignore
+    /// writes to read-only fields.
+    pub fn dispatch_write(&self, dev: &T, cache: &RegisterCache, offset: u64, size: u32, val: u64) {
+        let Some(idx) = self.find(offset) else {
+            return;
+        };
+        let reg = &self.registers[idx];
+        if offset + size as u64 > reg.offset + reg.width as u64 {
+            return;
+        }
+        if reg.access == Access::RO {
+            return;
+        }
+
+        let shift = (offset - reg.offset) * 8;
+        let mask = width_mask(size) << shift;
+        let incoming = (val << shift) & mask;
+        let cached = cache.0[idx].get();
+
+        let new_value = if reg.access == Access::W1C {
+            cached & !incoming
+        } else {
+            (cached & !mask) | incoming
+        };
+        cache.0[idx].set(new_value);
+
+        if let Some(write) = reg.write {
+            write(dev, cached, incoming >> shift);
+        }
+    }
+}
+
+const fn width_mask(size: u32) -> u64 {
+    if size >= 8 {
+        u64::MAX
+    } else {
+        (1u64 << (size * 8)) - 1
+    }
+}
+
+/// Debug-only check that `registers` is sorted by offset and that no two
+/// entries overlap.
+const fn debug_assert_no_overlap<T>(registers: &[RegisterDesc<T>]) {
+    #[cfg(debug_assertions)]
+    {
+        let mut i = 1;
+        while i < registers.len() {
+            let prev_end = registers[i - 1].offset + registers[i - 1].width as u64;
+            assert!(
+                registers[i].offset >= prev_end,
+                "RegisterMap entries must be sorted and non-overlapping"
+            );
+            i += 1;
+        }
+    }
+    #[cfg(not(debug_assertions))]
+    {
+        let _ = registers;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static MAP: RegisterMap<()> = RegisterMap::new(&[
+        RegisterDesc {
+            offset: 0,
+            width: 4,
+            access: Access::RW,
+            reset: 0,
+            read: None,
+            write: None,
+        },
+        RegisterDesc {
+            offset: 4,
+            width: 4,
+            access: Access::W1C,
+            reset: 0xff,
+            read: None,
+            write: None,
+        },
+    ]);
+
+    #[test]
+    #[should_panic(expected = "sorted and non-overlapping")]
+    fn overlap_is_rejected() {
+        let overlapping: &'static [RegisterDesc<()>] = &[
+            RegisterDesc {
+                offset: 0,
+                width: 4,
+                access: Access::RW,
+                reset: 0,
+                read: None,
+                write: None,
+            },
+            RegisterDesc {
+                offset: 2,
+                width: 4,
+                access: Access::RW,
+                reset: 0,
+                read: None,
+                write: None,
+            },
+        ];
+        RegisterMap::new(overlapping);
+    }
+
+    #[test]
+    fn narrow_access_is_masked_and_merged() {
+        let cache = MAP.new_cache();
+        MAP.dispatch_write(&(), &cache, 0, 4, 0x1234_5678);
+        assert_eq!(MAP.dispatch_read(&(), &cache, 0, 4), 0x1234_5678);
+
+        // A 1-byte write at offset 1 must only touch that byte.
+        MAP.dispatch_write(&(), &cache, 1, 1, 0xaa);
+        assert_eq!(MAP.dispatch_read(&(), &cache, 0, 4), 0x1234_aa78);
+        assert_eq!(MAP.dispatch_read(&(), &cache, 1, 1), 0xaa);
+    }
+
+    #[test]
+    fn out_of_bounds_access_is_dropped() {
+        let cache = MAP.new_cache();
+        MAP.dispatch_write(&(), &cache, 0, 4, 0xffff_ffff);
+
+        // An 8-byte read starting inside the 4-byte register at offset 0
+        // must not spill into the register at offset 4.
+        assert_eq!(MAP.dispatch_read(&(), &cache, 0, 8), 0);
+
+        MAP.dispatch_write(&(), &cache, 0, 8, 0x1);
+        assert_eq!(MAP.dispatch_read(&(), &cache, 0, 4), 0xffff_ffff);
+    }
+
+    #[test]
+    fn w1c_clears_only_written_bits() {
+        let cache = MAP.new_cache();
+        assert_eq!(MAP.dispatch_read(&(), &cache, 4, 4), 0xff);
+
+        MAP.dispatch_write(&(), &cache, 4, 4, 0x0f);
+        assert_eq!(MAP.dispatch_read(&(), &cache, 4, 4), 0xf0);
+    }
+}